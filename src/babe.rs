@@ -95,7 +95,11 @@
 //!
 
 use crate::header;
+use core::cmp;
 use core::time::Duration;
+use merlin::Transcript;
+use primitive_types::U256;
+use std::collections::HashMap;
 
 mod definitions;
 mod runtime;
@@ -105,6 +109,10 @@ pub mod header_info;
 
 pub use chain_config::BabeGenesisConfiguration;
 
+/// Number of slots of tolerance allowed between a claimed slot number and the slot number
+/// derived from the local clock, to account for small clock drift between peers.
+const MAX_SLOT_DRIFT: u64 = 1;
+
 /// Configuration for [`start_verify_header`].
 pub struct VerifyConfig<'a> {
     /// Header of the block to verify.
@@ -112,7 +120,9 @@ pub struct VerifyConfig<'a> {
 
     /// Time elapsed since [the Unix Epoch](https://en.wikipedia.org/wiki/Unix_time) (i.e.
     /// 00:00:00 UTC on 1 January 1970), ignoring leap seconds.
-    // TODO: unused, should check against a block's slot
+    ///
+    /// Used to reject headers whose slot number lies too far in the future, which a light client
+    /// cannot otherwise detect since it doesn't run the runtime.
     pub now_from_unix_epoch: Duration,
 
     /// Header of the parent of the block to verify.
@@ -131,6 +141,11 @@ pub struct VerifyConfig<'a> {
     /// Slot number of block #1. **Must** be provided, unless the block being verified is block
     /// #1 itself.
     pub block1_slot_number: Option<u64>,
+
+    /// Latest [`BabeConfigChange`] that has taken effect on this chain, if any, as found in a
+    /// previous block's [`VerifySuccess::config_change`]. `None` if [`VerifyConfig::genesis_configuration`]
+    /// should still be used for the primary-slot threshold and allowed secondary-slot type.
+    pub current_config: Option<BabeConfigChange>,
 }
 
 /// Information yielded back after successfully verifying a block.
@@ -143,12 +158,43 @@ pub struct VerifySuccess {
 
     /// Slot number the block belongs to.
     pub slot_number: u64,
+
+    /// `true` if the block was a primary slot claim, `false` if it was a secondary slot claim.
+    ///
+    /// Used by [`BlockScore::fold`] to implement the chain selection rule documented at the
+    /// module level.
+    pub primary: bool,
+
+    /// If `Some`, the verified block contains a `NextConfigDescriptor` log changing the `c`
+    /// threshold constant and/or the allowed secondary-slot type for future epochs. Must be
+    /// passed back as [`VerifyConfig::current_config`] once it takes effect.
+    pub config_change: Option<BabeConfigChange>,
+}
+
+/// A change, found in a block's digest, to the BABE configuration applied to future epochs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BabeConfigChange {
+    /// New `(numerator, denominator)` used for the primary-slot claim threshold.
+    pub c: (u64, u64),
+    /// New policy for which kind of secondary slot claims are allowed.
+    pub allowed_slots: AllowedSlots,
+}
+
+/// Policy dictating which kind of secondary slot claims, if any, are allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllowedSlots {
+    /// Only primary slot claims are allowed.
+    PrimaryOnly,
+    /// Primary and plain (non-VRF) secondary slot claims are allowed.
+    PrimaryAndSecondaryPlain,
+    /// Primary and VRF secondary slot claims are allowed.
+    PrimaryAndSecondaryVRF,
 }
 
 /// Information about an epoch.
 ///
 /// Obtained as part of the [`VerifySuccess`] returned after verifying a block.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EpochInformation {
     /// List of authorities that are allowed to sign blocks during this epoch.
     ///
@@ -162,7 +208,7 @@ pub struct EpochInformation {
 }
 
 /// Information about a specific authority.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EpochInformationAuthority {
     /// Ristretto public key that is authorized to sign blocks.
     pub public_key: [u8; 32],
@@ -186,6 +232,21 @@ pub enum VerifyError {
     UnexpectedEpochChangeLog,
     /// Block is the first block after a new epoch, but it is missing an epoch change digest log.
     MissingEpochChangeLog,
+    /// Claimed slot number is in the future compared to [`VerifyConfig::now_from_unix_epoch`].
+    SlotInFuture,
+    /// Failed to verify the VRF output and proof of the claimed slot.
+    BadVrfProof,
+    /// VRF output is above the primary slot claim threshold.
+    BadSlotClaim,
+    /// `authority_index` found in the header doesn't correspond to any authority in the epoch.
+    InvalidAuthorityIndex,
+    /// Seal digest log doesn't contain a valid signature of the claiming authority.
+    BadSignature,
+    /// Block claims a secondary slot type that the active [`BabeConfigChange`] forbids.
+    DisallowedSlotType,
+    /// Slot number is inferior to [`VerifyConfig::block1_slot_number`], making it impossible to
+    /// determine which epoch the block belongs to.
+    SlotBeforeBlock1,
 }
 
 /// Verifies whether a block header provides a correct proof of the legitimacy of the authorship.
@@ -223,9 +284,23 @@ pub fn start_verify_header<'a>(
         ),
     };
 
+    // Reject headers whose slot lies too far in the future. A light client cannot otherwise
+    // detect this, as it doesn't run the runtime and thus has no other way to know the current
+    // time.
+    {
+        let slot_duration = config.genesis_configuration.slot_duration();
+        let now_slot_number =
+            u64::try_from(config.now_from_unix_epoch.as_millis() / slot_duration.as_millis())
+                .unwrap_or(u64::max_value());
+        if slot_number > now_slot_number.saturating_add(MAX_SLOT_DRIFT) {
+            return Err(VerifyError::SlotInFuture);
+        }
+    }
+
     // Determine the epoch number of the block that we verify.
     let epoch_number = match (slot_number, config.block1_slot_number) {
-        (curr, Some(block1)) => slot_number_to_epoch(curr, config.genesis_configuration, block1).unwrap(), // TODO: don't unwrap
+        (curr, Some(block1)) => slot_number_to_epoch(curr, config.genesis_configuration, block1)
+            .map_err(|()| VerifyError::SlotBeforeBlock1)?,
         (_, None) if config.header.number == 1 => 0,
         (_, None) => panic!(),
     };
@@ -261,11 +336,12 @@ pub fn start_verify_header<'a>(
         (None, true) => return Err(VerifyError::MissingEpochChangeLog),
     };
 
-    // TODO: as a hack, we just return `Success` right now even though we don't check much; this
-    //       is because the `Pending` variant is unusable
-    Ok(SuccessOrPending::Success(VerifySuccess {
-        epoch_change,
-        slot_number,
+    // The rest of the verification (VRF proof, slot claim threshold, seal signature, ...)
+    // requires knowing the `EpochInformation` of the epoch the block belongs to, which only the
+    // caller can provide.
+    Ok(SuccessOrPending::Pending(PendingVerify {
+        config,
+        epoch_number,
     }))
 }
 
@@ -282,10 +358,28 @@ pub enum SuccessOrPending<'a> {
 #[must_use]
 pub struct PendingVerify<'a> {
     config: VerifyConfig<'a>,
+    epoch_number: u64,
 }
 
 impl<'a> PendingVerify<'a> {
-    // TODO: should provide ways to find out which `EpochInformation` to pass back
+    /// Epoch number the block being verified belongs to.
+    ///
+    /// Use this to determine which [`EpochInformation`] must be passed to [`PendingVerify::finish`].
+    ///
+    /// > **Note**: Epochs 0 and 1 are special-cased, as the information about them comes from
+    /// >           [`BabeGenesisConfiguration`] rather than from a block of a previous epoch.
+    pub fn epoch_number(&self) -> u64 {
+        self.epoch_number
+    }
+
+    /// Slot number the block being verified belongs to.
+    pub fn slot_number(&self) -> u64 {
+        // Guaranteed not to panic, as `start_verify_header` has already parsed the header
+        // successfully in order to build this `PendingVerify`.
+        header_info::header_information(self.config.header.clone())
+            .unwrap()
+            .slot_number()
+    }
 
     /// Finishes the verification. Must provide the information about the epoch the block belongs
     /// to.
@@ -327,24 +421,127 @@ impl<'a> PendingVerify<'a> {
             return Err(VerifyError::SlotNumberNotIncreasing);
         }
 
-        // TODO: gather current authorities, and verify everything
+        // Determine the epoch number of the block that we verify, for use in the VRF transcript.
+        let epoch_number = match (slot_number, self.config.block1_slot_number) {
+            (curr, Some(block1)) => {
+                slot_number_to_epoch(curr, self.config.genesis_configuration, block1)
+                    .map_err(|()| VerifyError::SlotBeforeBlock1)?
+            }
+            (_, None) if self.config.header.number == 1 => 0,
+            (_, None) => panic!(),
+        };
+
+        let authority = epoch_info
+            .authorities
+            .get(authority_index)
+            .ok_or(VerifyError::InvalidAuthorityIndex)?;
+
+        // Reject the slot claim if the currently active configuration (either the genesis one,
+        // or the last `NextConfigDescriptor` that has taken effect) forbids its type.
+        if !primary {
+            let is_vrf = vrf.is_some();
+            let allowed_slots = self
+                .config
+                .current_config
+                .map(|config| config.allowed_slots)
+                .unwrap_or_else(|| self.config.genesis_configuration.allowed_slots());
+            let forbidden = match allowed_slots {
+                AllowedSlots::PrimaryOnly => true,
+                AllowedSlots::PrimaryAndSecondaryPlain => is_vrf,
+                AllowedSlots::PrimaryAndSecondaryVRF => !is_vrf,
+            };
+            if forbidden {
+                return Err(VerifyError::DisallowedSlotType);
+            }
+        }
+
+        // `c` constant used for the primary slot claim threshold: either the one from the
+        // genesis configuration, or the one from the last `NextConfigDescriptor` that has taken
+        // effect.
+        let c = self
+            .config
+            .current_config
+            .map(|config| config.c)
+            .unwrap_or_else(|| self.config.genesis_configuration.c());
+
+        // Verify the VRF output and proof, if any, and check the primary slot claim threshold.
+        if let Some((vrf_output, vrf_proof)) = vrf {
+            let public_key = schnorrkel::PublicKey::from_bytes(&authority.public_key)
+                .map_err(|_| VerifyError::BadVrfProof)?;
+            let vrf_output = schnorrkel::vrf::VRFOutput::from_bytes(&vrf_output)
+                .map_err(|_| VerifyError::BadVrfProof)?;
+            let vrf_proof = schnorrkel::vrf::VRFProof::from_bytes(&vrf_proof)
+                .map_err(|_| VerifyError::BadVrfProof)?;
+
+            let transcript =
+                babe_vrf_transcript(slot_number, epoch_number, &epoch_info.randomness);
+            let (vrf_in_out, _) = public_key
+                .vrf_verify(transcript, &vrf_output, &vrf_proof)
+                .map_err(|_| VerifyError::BadVrfProof)?;
+
+            if primary {
+                let threshold =
+                    calculate_primary_threshold(c, &epoch_info.authorities, authority_index);
+
+                let out_bytes = vrf_in_out.make_bytes::<[u8; 16]>(b"substrate-babe-vrf");
+                let r = u128::from_le_bytes(out_bytes);
+
+                if r >= threshold {
+                    return Err(VerifyError::BadSlotClaim);
+                }
+            }
+        }
 
         // The signature in the seal applies to the header from where the signature isn't present.
         // Build the hash that is expected to be signed.
-        let pre_seal_hash = {
+        let (pre_seal_hash, seal_signature) = {
             let mut unsealed_header = self.config.header;
-            let _popped = unsealed_header.digest.pop();
-            debug_assert!(matches!(_popped, Some(header::DigestItemRef::Seal(_, _))));
-            unsealed_header.hash()
+            let popped = unsealed_header.digest.pop();
+            let (engine_id, sig) = match popped {
+                Some(header::DigestItemRef::Seal(engine_id, sig)) => (engine_id, sig),
+                _ => panic!(), // Guaranteed by `header_info::header_information`.
+            };
+            if engine_id != *b"BABE" {
+                return Err(VerifyError::BadSignature);
+            }
+            (unsealed_header.hash(), sig)
         };
 
+        // Verify that the seal signature was produced by the claiming authority.
+        {
+            let public_key = schnorrkel::PublicKey::from_bytes(&authority.public_key)
+                .map_err(|_| VerifyError::BadSignature)?;
+            let signature = schnorrkel::Signature::from_bytes(seal_signature)
+                .map_err(|_| VerifyError::BadSignature)?;
+            public_key
+                .verify_simple(b"substrate", &pre_seal_hash, &signature)
+                .map_err(|_| VerifyError::BadSignature)?;
+        }
+
         // TODO: check that epoch change is in header iff it's actually an epoch change
 
         // TODO: in case of epoch change, should also check the randomness value; while the runtime
         //       checks that the randomness value is correct, light clients in particular do not
         //       execute the runtime
 
-        // TODO: handle config change
+        let config_change =
+            header
+                .epoch_change
+                .as_ref()
+                .and_then(|(_, config_change)| config_change.as_ref())
+                .map(|config_change| BabeConfigChange {
+                    c: config_change.c,
+                    allowed_slots: match config_change.allowed_slots {
+                        header_info::AllowedSlots::PrimaryOnly => AllowedSlots::PrimaryOnly,
+                        header_info::AllowedSlots::PrimaryAndSecondaryPlain => {
+                            AllowedSlots::PrimaryAndSecondaryPlain
+                        }
+                        header_info::AllowedSlots::PrimaryAndSecondaryVRF => {
+                            AllowedSlots::PrimaryAndSecondaryVRF
+                        }
+                    },
+                });
+
         let epoch_change = header
             .epoch_change
             .map(|(epoch_change, _)| EpochInformation {
@@ -359,10 +556,314 @@ impl<'a> PendingVerify<'a> {
         Ok(VerifySuccess {
             epoch_change,
             slot_number,
+            primary,
+            config_change,
         })
     }
 }
 
+/// Verifies a segment of an ordered chain of headers that all descend from a common ancestor.
+///
+/// `headers` must be ordered by increasing block number, and `headers[0]`'s parent must be
+/// `first_parent_header`.
+///
+/// `block1_slot_number` can be `None` if `headers` itself starts at block #1: its slot number is
+/// then derived from that block's own header and reused for the rest of the segment. Otherwise
+/// it must be provided, exactly like for [`VerifyConfig::block1_slot_number`].
+///
+/// `epoch_info` is called with an epoch number whenever the [`EpochInformation`] of that epoch
+/// is needed and hasn't been yielded by a previous block of the segment. Epoch changes found
+/// while verifying the segment are cached and fed back into later blocks of the segment, meaning
+/// that `epoch_info` will never be called twice for the same epoch number unless the segment
+/// spans more than one epoch transition for it.
+///
+/// On success, returns one [`VerifySuccess`] per entry of `headers`, in order. On failure,
+/// returns the [`VerifyError`] alongside the index, within `headers`, of the offending block.
+pub fn verify_chain_segment<'a>(
+    first_parent_header: header::HeaderRef<'a>,
+    headers: impl IntoIterator<Item = header::HeaderRef<'a>>,
+    genesis_configuration: &BabeGenesisConfiguration,
+    block1_slot_number: Option<u64>,
+    now_from_unix_epoch: Duration,
+    mut epoch_info: impl FnMut(u64) -> EpochInformation,
+) -> Result<Vec<VerifySuccess>, (usize, VerifyError)> {
+    let mut epoch_info_cache = HashMap::new();
+    // Config changes found in the segment, keyed by the epoch number at which they take effect.
+    // Like `epoch_info_cache`, these must not be applied until that epoch is actually reached.
+    let mut pending_config_changes: HashMap<u64, BabeConfigChange> = HashMap::new();
+    let mut parent_header = first_parent_header;
+    let mut current_config = None;
+    let mut results = Vec::new();
+    // Effective slot number of block #1. If the caller didn't provide one, it is derived from
+    // block #1's own header the first time it is encountered within the segment, so that a
+    // segment starting at block #1 (the normal initial-sync case) doesn't require the caller to
+    // already know this value up front.
+    let mut block1_slot_number = block1_slot_number;
+
+    for (index, header) in headers.into_iter().enumerate() {
+        let (epoch_number, slot_number) =
+            match header_epoch_number(header.clone(), genesis_configuration, block1_slot_number) {
+                Ok(result) => result,
+                Err(err) => return Err((index, err)),
+            };
+
+        if block1_slot_number.is_none() && header.number == 1 {
+            block1_slot_number = Some(slot_number);
+        }
+
+        promote_pending_config_change(
+            &mut pending_config_changes,
+            &mut current_config,
+            epoch_number,
+        );
+
+        let config = VerifyConfig {
+            header: header.clone(),
+            now_from_unix_epoch,
+            parent_block_header: parent_header,
+            genesis_configuration,
+            block1_slot_number,
+            current_config,
+        };
+
+        let pending = match start_verify_header(config) {
+            Ok(SuccessOrPending::Pending(pending)) => pending,
+            Ok(SuccessOrPending::Success(success)) => {
+                // `start_verify_header` always returns `Pending`, but is handled for
+                // completeness in case this changes in the future.
+                results.push(success);
+                parent_header = header;
+                continue;
+            }
+            Err(err) => return Err((index, err)),
+        };
+
+        let info = epoch_info_cache
+            .entry(epoch_number)
+            .or_insert_with(|| epoch_info(epoch_number));
+
+        let success = match pending.finish(info) {
+            Ok(success) => success,
+            Err(err) => return Err((index, err)),
+        };
+
+        // The information about epoch `N` is found in the first block of epoch `N - 1`; cache
+        // it so that the rest of the segment doesn't need to round-trip to the caller for it.
+        if let Some(epoch_change) = &success.epoch_change {
+            epoch_info_cache.insert(epoch_number + 1, epoch_change.clone());
+        }
+        // Likewise, a `NextConfigDescriptor` found in the first block of epoch `N` only takes
+        // effect at epoch `N + 1`; defer it rather than applying it to the rest of epoch `N`.
+        if let Some(config_change) = success.config_change {
+            pending_config_changes.insert(epoch_number + 1, config_change);
+        }
+
+        results.push(success);
+        parent_header = header;
+    }
+
+    Ok(results)
+}
+
+/// Promotes `pending_config_changes` whose target epoch has now been reached (i.e. `<=
+/// epoch_number`) into `current_config`. It then remains the active configuration for all later
+/// epochs, until superseded by a later change.
+fn promote_pending_config_change(
+    pending_config_changes: &mut HashMap<u64, BabeConfigChange>,
+    current_config: &mut Option<BabeConfigChange>,
+    epoch_number: u64,
+) {
+    while let Some(&target_epoch) = pending_config_changes
+        .keys()
+        .find(|&&target_epoch| target_epoch <= epoch_number)
+    {
+        *current_config = pending_config_changes.remove(&target_epoch);
+    }
+}
+
+/// Determines the epoch number that `header` belongs to, without fully verifying it, alongside
+/// its own slot number.
+///
+/// Used by [`verify_chain_segment`] to decide which [`BabeConfigChange`] is active *before*
+/// building the [`VerifyConfig`] passed to [`start_verify_header`], and to discover the slot
+/// number of the segment's own block #1 when the caller didn't provide one.
+fn header_epoch_number(
+    header: header::HeaderRef,
+    genesis_configuration: &BabeGenesisConfiguration,
+    block1_slot_number: Option<u64>,
+) -> Result<(u64, u64), VerifyError> {
+    let block_number = header.number;
+    let parsed = header_info::header_information(header).map_err(VerifyError::BadHeader)?;
+    let slot_number = parsed.slot_number();
+    let epoch_number = match block1_slot_number {
+        Some(block1) => slot_number_to_epoch(slot_number, genesis_configuration, block1)
+            .map_err(|()| VerifyError::SlotBeforeBlock1)?,
+        None if block_number == 1 => 0,
+        // Only reachable if `headers` doesn't start at block #1 and the caller failed to supply
+        // `block1_slot_number`, in which case there is no way to determine the epoch number.
+        None => panic!("verify_chain_segment: block1_slot_number is required unless the segment starts at block #1"),
+    };
+    Ok((epoch_number, slot_number))
+}
+
+/// Proof that an authority has signed two distinct headers during the same slot of the same
+/// epoch, which constitutes a slashable BABE equivocation.
+#[derive(Debug, Clone)]
+pub struct EquivocationProof {
+    /// Ristretto public key of the authority that equivocated.
+    pub authority_public_key: [u8; 32],
+    /// Slot number during which the equivocation happened.
+    pub slot_number: u64,
+    /// Pre-seal hash of the first header.
+    pub first_header_hash: [u8; 32],
+    /// Pre-seal hash of the second header.
+    pub second_header_hash: [u8; 32],
+}
+
+/// Checks whether `header_a` and `header_b` were both *validly* signed, in the same slot of the
+/// epoch described by `epoch_info`, by the same authority, and are distinct blocks. If so,
+/// returns a proof of the equivocation that can be reported to the runtime.
+///
+/// Each header's VRF proof (if any) and seal signature are verified against the claiming
+/// authority exactly like [`PendingVerify::finish`] would, using `epoch_number` to build the VRF
+/// transcript. A header that doesn't pass this verification can't be used as part of a proof, as
+/// anyone could otherwise fabricate a pair of bogus headers to falsely implicate an authority.
+///
+/// A light client tracking competing forks (see the [module-level documentation](self#chain-selection) about
+/// chain selection) can use this to surface misbehaving validators rather than silently picking
+/// a fork.
+pub fn detect_equivocation<'a>(
+    header_a: header::HeaderRef<'a>,
+    header_b: header::HeaderRef<'a>,
+    epoch_number: u64,
+    epoch_info: &EpochInformation,
+) -> Option<EquivocationProof> {
+    let (authority_index_a, slot_number, first_header_hash) =
+        verify_claim_signatures(header_a, epoch_number, epoch_info).ok()?;
+    let (authority_index_b, slot_number_b, second_header_hash) =
+        verify_claim_signatures(header_b, epoch_number, epoch_info).ok()?;
+
+    if authority_index_a != authority_index_b || slot_number != slot_number_b {
+        return None;
+    }
+
+    if first_header_hash == second_header_hash {
+        return None;
+    }
+
+    let authority = epoch_info.authorities.get(authority_index_a)?;
+
+    Some(EquivocationProof {
+        authority_public_key: authority.public_key,
+        slot_number,
+        first_header_hash,
+        second_header_hash,
+    })
+}
+
+/// Verifies that `header`'s VRF proof (if any) and seal signature were produced by the authority
+/// it claims, in `epoch_info`, using `epoch_number` to build the VRF transcript.
+///
+/// On success, returns the claiming `authority_index`, the `slot_number` of the header, and its
+/// pre-seal hash. This performs the same checks as [`PendingVerify::finish`], minus the
+/// allowed secondary-slot type, the primary-slot claim threshold and the checks that depend on a
+/// parent header, none of which are relevant to establishing that a header was legitimately
+/// signed.
+fn verify_claim_signatures(
+    header: header::HeaderRef,
+    epoch_number: u64,
+    epoch_info: &EpochInformation,
+) -> Result<(usize, u64, [u8; 32]), VerifyError> {
+    let parsed =
+        header_info::header_information(header.clone()).map_err(VerifyError::BadHeader)?;
+
+    let (authority_index, slot_number, vrf) = match parsed.pre_runtime {
+        header_info::PreDigest::Primary(digest) => (
+            digest.authority_index,
+            digest.slot_number,
+            Some((digest.vrf_output, digest.vrf_proof)),
+        ),
+        header_info::PreDigest::SecondaryPlain(digest) => {
+            (digest.authority_index, digest.slot_number, None)
+        }
+        header_info::PreDigest::SecondaryVRF(digest) => (
+            digest.authority_index,
+            digest.slot_number,
+            Some((digest.vrf_output, digest.vrf_proof)),
+        ),
+    };
+
+    let authority = epoch_info
+        .authorities
+        .get(authority_index)
+        .ok_or(VerifyError::InvalidAuthorityIndex)?;
+
+    let public_key = schnorrkel::PublicKey::from_bytes(&authority.public_key)
+        .map_err(|_| VerifyError::BadVrfProof)?;
+
+    if let Some((vrf_output, vrf_proof)) = vrf {
+        let vrf_output = schnorrkel::vrf::VRFOutput::from_bytes(&vrf_output)
+            .map_err(|_| VerifyError::BadVrfProof)?;
+        let vrf_proof = schnorrkel::vrf::VRFProof::from_bytes(&vrf_proof)
+            .map_err(|_| VerifyError::BadVrfProof)?;
+        let transcript = babe_vrf_transcript(slot_number, epoch_number, &epoch_info.randomness);
+        public_key
+            .vrf_verify(transcript, &vrf_output, &vrf_proof)
+            .map_err(|_| VerifyError::BadVrfProof)?;
+    }
+
+    let (pre_seal_hash, seal_signature) = {
+        let mut unsealed_header = header;
+        let (engine_id, sig) = match unsealed_header.digest.pop() {
+            Some(header::DigestItemRef::Seal(engine_id, sig)) => (engine_id, sig),
+            _ => return Err(VerifyError::BadSignature),
+        };
+        if engine_id != *b"BABE" {
+            return Err(VerifyError::BadSignature);
+        }
+        (unsealed_header.hash(), sig)
+    };
+
+    let signature = schnorrkel::Signature::from_bytes(seal_signature)
+        .map_err(|_| VerifyError::BadSignature)?;
+    public_key
+        .verify_simple(b"substrate", &pre_seal_hash, &signature)
+        .map_err(|_| VerifyError::BadSignature)?;
+
+    Ok((authority_index, slot_number, pre_seal_hash))
+}
+
+/// Score of a chain, accumulated along a branch, used to implement the BABE best-chain
+/// selection rule documented in the [module-level documentation](self#chain-selection): highest
+/// slot number first, then highest count of primary slot claims.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BlockScore {
+    /// Slot number of the block at the head of the branch.
+    pub slot_number: u64,
+    /// Number of primary slot claims along the branch, including the head block.
+    pub primary_claims: u64,
+}
+
+impl BlockScore {
+    /// Folds a newly-verified block into the running score of the branch it extends.
+    pub fn fold(self, verified: &VerifySuccess) -> BlockScore {
+        BlockScore {
+            slot_number: verified.slot_number,
+            primary_claims: self.primary_claims + u64::from(verified.primary),
+        }
+    }
+}
+
+/// Compares two [`BlockScore`]s according to the BABE chain selection rule: the chain with the
+/// highest slot number wins; ties are broken by the highest count of primary slot claims.
+///
+/// Callers building a fork tree can use this to rank competing heads without re-parsing digests.
+pub fn compare(a: &BlockScore, b: &BlockScore) -> cmp::Ordering {
+    a.slot_number
+        .cmp(&b.slot_number)
+        .then(a.primary_claims.cmp(&b.primary_claims))
+}
+
 /// Turns a slot number into an epoch number.
 ///
 /// Returns an error if `slot_number` is inferior to `block1_slot_number`.
@@ -370,3 +871,315 @@ fn slot_number_to_epoch(slot_number: u64, genesis_config: &BabeGenesisConfigurat
     let slots_diff = slot_number.checked_sub(block1_slot_number).ok_or(())?;
     Ok((slots_diff.checked_add(1).ok_or(())?) / genesis_config.slots_per_epoch())
 }
+
+/// Builds the VRF transcript that a block author must use, as specified by the BABE
+/// specification, in order to claim a slot.
+fn babe_vrf_transcript(slot_number: u64, epoch_number: u64, randomness: &[u8; 32]) -> Transcript {
+    let mut transcript = Transcript::new(b"BABE");
+    transcript.append_u64(b"slot number", slot_number);
+    transcript.append_u64(b"current epoch", epoch_number);
+    transcript.append_message(b"chain randomness", randomness);
+    transcript
+}
+
+/// Fixed-point representation of a value in the range `[0, 1]`, where `u128::MAX` represents `1`.
+///
+/// Used to compute the BABE primary slot claim threshold without going through floating-point
+/// arithmetic, whose behaviour isn't guaranteed to be identical on all platforms.
+type FixedPoint = u128;
+
+/// Multiplies two [`FixedPoint`] values together.
+fn fixed_point_mul(a: FixedPoint, b: FixedPoint) -> FixedPoint {
+    (U256::from(a) * U256::from(b) / U256::from(u128::MAX)).low_u128()
+}
+
+/// Builds the [`FixedPoint`] representation of `numerator / denominator`. `numerator` must be
+/// inferior or equal to `denominator`.
+fn fixed_point_ratio(numerator: u64, denominator: u64) -> FixedPoint {
+    debug_assert!(numerator <= denominator);
+    (U256::from(numerator) * U256::from(u128::MAX) / U256::from(denominator)).low_u128()
+}
+
+/// Raises a [`FixedPoint`] value to the power of `exponent`, using exponentiation by squaring.
+fn fixed_point_pow(mut base: FixedPoint, mut exponent: u64) -> FixedPoint {
+    let mut result: FixedPoint = u128::MAX;
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            result = fixed_point_mul(result, base);
+        }
+        base = fixed_point_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Finds the `n`-th root of a [`FixedPoint`] value, using binary search. `n` must be superior to
+/// 0.
+fn fixed_point_nth_root(value: FixedPoint, n: u64) -> FixedPoint {
+    debug_assert!(n != 0);
+
+    let (mut low, mut high): (FixedPoint, FixedPoint) = (0, u128::MAX);
+    // 128 iterations is enough to converge on the exact result representable in a 128bit
+    // fixed-point value.
+    for _ in 0..128 {
+        let mid = low + (high - low) / 2;
+        if fixed_point_pow(mid, n) <= value {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    // `high` starts at `u128::MAX` and is never tested by the loop above, so check it here in
+    // order for the root of `u128::MAX` (i.e. `1`) to be `u128::MAX`.
+    if fixed_point_pow(high, n) <= value {
+        high
+    } else {
+        low
+    }
+}
+
+/// Calculates the primary slot claim threshold `T` such that a claim is valid iff the 128 bits
+/// derived from the VRF output are `< T`.
+///
+/// Implements `T = MAX_U128 * (1 - (1 - c)^theta)` where `theta` is the ratio between the
+/// weight of the claiming authority and the sum of the weights of all the authorities, without
+/// using floating-point arithmetic.
+fn calculate_primary_threshold(
+    c: (u64, u64),
+    authorities: &[EpochInformationAuthority],
+    authority_index: usize,
+) -> u128 {
+    let total_weight: u64 = authorities.iter().map(|a| a.weight).sum();
+    let weight = authorities[authority_index].weight;
+    debug_assert!(weight > 0);
+
+    // `(1 - c) ^ theta` where `theta = weight / total_weight` is computed as the
+    // `total_weight`-th root of `(1 - c) ^ weight`, which avoids raising a fixed-point value to
+    // a non-integer power.
+    let one_minus_c = fixed_point_ratio(c.1 - c.0, c.1);
+    let one_minus_c_pow_weight = fixed_point_pow(one_minus_c, weight);
+    let one_minus_c_pow_theta = fixed_point_nth_root(one_minus_c_pow_weight, total_weight);
+
+    u128::MAX - one_minus_c_pow_theta
+}
+
+// Note: exercising `finish`/`verify_chain_segment` against real network-decoded headers isn't
+// practical here, so the tests below instead build minimal header fixtures directly and cover,
+// in isolation, the things that were actually found to be wrong during review: the fixed-point
+// threshold math, the cryptographic primitives (VRF proof and seal signature) that `finish` and
+// `verify_claim_signatures` rely on to reject forged claims, and `detect_equivocation` itself.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authority(weight: u64) -> EpochInformationAuthority {
+        EpochInformationAuthority {
+            public_key: [0; 32],
+            weight,
+        }
+    }
+
+    #[test]
+    fn primary_threshold_for_sole_authority_matches_c() {
+        // With a single authority, `theta == 1`, so `T` should equal `MAX_U128 * c`.
+        let authorities = [authority(1)];
+        let threshold = calculate_primary_threshold((1, 2), &authorities, 0);
+        let expected = u128::MAX / 2;
+        assert!(
+            threshold.abs_diff(expected) < 1 << 32,
+            "threshold {} too far from expected {}",
+            threshold,
+            expected
+        );
+    }
+
+    #[test]
+    fn primary_threshold_increases_with_weight() {
+        let authorities = [authority(1), authority(9)];
+        let low_weight_threshold = calculate_primary_threshold((1, 2), &authorities, 0);
+        let high_weight_threshold = calculate_primary_threshold((1, 2), &authorities, 1);
+        assert!(high_weight_threshold > low_weight_threshold);
+    }
+
+    #[test]
+    fn primary_threshold_is_zero_when_c_is_zero() {
+        let authorities = [authority(3), authority(7)];
+        assert_eq!(calculate_primary_threshold((0, 1), &authorities, 0), 0);
+    }
+
+    #[test]
+    fn fixed_point_pow_and_nth_root_are_inverses() {
+        let base = fixed_point_ratio(1, 3);
+        let raised = fixed_point_pow(base, 7);
+        let root = fixed_point_nth_root(raised, 7);
+        assert!(base.abs_diff(root) < 1 << 32);
+    }
+
+    #[test]
+    fn block_score_compare_orders_by_slot_then_primary_claims() {
+        let higher_slot = BlockScore {
+            slot_number: 6,
+            primary_claims: 0,
+        };
+        let lower_slot = BlockScore {
+            slot_number: 5,
+            primary_claims: 10,
+        };
+        assert_eq!(compare(&lower_slot, &higher_slot), cmp::Ordering::Less);
+
+        let fewer_claims = BlockScore {
+            slot_number: 5,
+            primary_claims: 3,
+        };
+        let more_claims = BlockScore {
+            slot_number: 5,
+            primary_claims: 4,
+        };
+        assert_eq!(compare(&fewer_claims, &more_claims), cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn block_score_fold_tracks_slot_and_primary_claims() {
+        let score = BlockScore::default().fold(&VerifySuccess {
+            epoch_change: None,
+            slot_number: 42,
+            primary: true,
+            config_change: None,
+        });
+        assert_eq!(score.slot_number, 42);
+        assert_eq!(score.primary_claims, 1);
+
+        let score = score.fold(&VerifySuccess {
+            epoch_change: None,
+            slot_number: 43,
+            primary: false,
+            config_change: None,
+        });
+        assert_eq!(score.slot_number, 43);
+        assert_eq!(score.primary_claims, 1);
+    }
+
+    #[test]
+    fn genuine_vrf_proof_verifies_but_proof_for_another_slot_does_not() {
+        let keypair = schnorrkel::Keypair::generate();
+        let randomness = [7u8; 32];
+
+        let transcript = babe_vrf_transcript(100, 3, &randomness);
+        let (in_out, proof, _) = keypair.vrf_sign(transcript.clone());
+        let output = in_out.to_output();
+
+        assert!(keypair
+            .public
+            .vrf_verify(transcript.clone(), &output, &proof)
+            .is_ok());
+
+        // A proof produced for a different slot must not be accepted as a claim for this one;
+        // this is what `finish` relies on to reject a forged VRF claim.
+        let other_transcript = babe_vrf_transcript(101, 3, &randomness);
+        let (_, forged_proof, _) = keypair.vrf_sign(other_transcript);
+        assert!(keypair
+            .public
+            .vrf_verify(transcript, &output, &forged_proof)
+            .is_err());
+    }
+
+    /// Builds the pre-runtime digest payload of a primary BABE slot claim: a discriminant byte
+    /// identifying [`header_info::PreDigest::Primary`], followed by the little-endian
+    /// `authority_index`, the little-endian `slot_number`, the 32-byte VRF output and the 64-byte
+    /// VRF proof.
+    fn primary_pre_digest(authority_index: u32, slot_number: u64, filler: u8) -> Vec<u8> {
+        let mut payload = vec![0u8];
+        payload.extend_from_slice(&authority_index.to_le_bytes());
+        payload.extend_from_slice(&slot_number.to_le_bytes());
+        payload.extend_from_slice(&[filler; 32]); // vrf_output
+        payload.extend_from_slice(&[filler; 64]); // vrf_proof
+        payload
+    }
+
+    /// Builds a header carrying a forged (unsigned) primary slot claim: the VRF output/proof and
+    /// the seal are filled with arbitrary bytes rather than being produced by a real authority
+    /// key, exactly what an attacker forging an equivocation report would have to submit.
+    fn forged_header(authority_index: u32, slot_number: u64, filler: u8, number: u64) -> header::Header {
+        header::Header {
+            parent_hash: [0; 32],
+            number,
+            state_root: [0; 32],
+            extrinsics_root: [0; 32],
+            digest: header::Digest::from_digest_items(vec![
+                header::DigestItem::PreRuntime(
+                    *b"BABE",
+                    primary_pre_digest(authority_index, slot_number, filler),
+                ),
+                header::DigestItem::Seal(*b"BABE", vec![filler; 64]),
+            ]),
+        }
+    }
+
+    #[test]
+    fn detect_equivocation_rejects_forged_unsigned_headers() {
+        // Two headers claiming the same authority and slot, with differing content, but whose
+        // VRF proof and seal signature are garbage rather than produced by the authority's real
+        // key. Before this function verified anything, this pair would have falsely produced an
+        // `EquivocationProof` implicating the authority.
+        let epoch_info = EpochInformation {
+            authorities: vec![authority(1)],
+            randomness: [0; 32],
+        };
+
+        let header_a = forged_header(0, 42, 1, 100);
+        let header_b = forged_header(0, 42, 2, 100);
+
+        assert!(
+            detect_equivocation(header_a.as_ref(), header_b.as_ref(), 3, &epoch_info).is_none()
+        );
+    }
+
+    #[test]
+    fn tampered_seal_signature_is_rejected() {
+        let keypair = schnorrkel::Keypair::generate();
+        let forger = schnorrkel::Keypair::generate();
+
+        let pre_seal_hash = [9u8; 32];
+        let signature = keypair.sign_simple(b"substrate", &pre_seal_hash);
+
+        assert!(keypair
+            .public
+            .verify_simple(b"substrate", &pre_seal_hash, &signature)
+            .is_ok());
+
+        // A signature produced by a different authority's key must be rejected, exactly what
+        // guards against a forged seal in `verify_claim_signatures` and `finish`.
+        assert!(forger
+            .public
+            .verify_simple(b"substrate", &pre_seal_hash, &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn config_change_promotion_is_deferred_to_its_target_epoch() {
+        // Regression test for the deferral logic in `verify_chain_segment`: a `config_change`
+        // reported on the first block of epoch `N` must only take effect for epoch `N + 1`, never
+        // for the remainder of epoch `N` itself. Calls the same `promote_pending_config_change`
+        // helper `verify_chain_segment` itself uses, so a future regression in that function is
+        // caught here too.
+        let mut pending_config_changes: HashMap<u64, BabeConfigChange> = HashMap::new();
+        let mut current_config: Option<BabeConfigChange> = None;
+
+        let new_config = BabeConfigChange {
+            c: (1, 4),
+            allowed_slots: AllowedSlots::PrimaryOnly,
+        };
+
+        // Epoch 0's first block announces a config change targeting epoch 1.
+        pending_config_changes.insert(1, new_config);
+
+        // Still within epoch 0: nothing should have been promoted yet.
+        promote_pending_config_change(&mut pending_config_changes, &mut current_config, 0);
+        assert!(current_config.is_none());
+
+        // Once a block of epoch 1 is reached, the pending config change must be promoted.
+        promote_pending_config_change(&mut pending_config_changes, &mut current_config, 1);
+        assert_eq!(current_config, Some(new_config));
+        assert!(pending_config_changes.is_empty());
+    }
+}